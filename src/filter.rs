@@ -1,31 +1,118 @@
+use chrono;
 use ethers::types::{Address, Bytes, U256};
+use ethers::utils::keccak256;
 use hex;
-use serde::Serialize;
-use serde_repr::Serialize_repr;
-use std::{cell::RefCell, rc::Rc};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use std::{cell::RefCell, fmt, rc::Rc};
 
 // Sources
 // * https://developerlife.com/2022/02/24/rust-non-binary-tree/
 
-#[derive(Clone, Copy, Debug, Serialize_repr)]
+#[derive(Clone, Copy, Debug, Serialize_repr, Deserialize_repr)]
 #[repr(u8)]
 pub enum Operator {
     AND = 1,
     OR = 2,
 }
 
-#[derive(Clone, Debug, Serialize)]
-#[serde(rename_all(serialize = "PascalCase"))]
+/// Errors returned while validating operand values passed to the builder.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilterError {
+    /// The decoded value was not the expected byte length (e.g. a method ID
+    /// that isn't exactly 4 bytes, or a hex string that isn't 20 bytes).
+    InvalidLength { expected: usize, actual: usize },
+    /// The input wasn't valid hex: it contained non-hex characters, or (for
+    /// a method ID) an odd number of hex digits.
+    InvalidHex(String),
+    /// The address's mixed-case characters don't match the EIP-55 checksum.
+    InvalidChecksum,
+    /// A `FilterValue::timestamp` input didn't match the given format string,
+    /// or a `FilterValue::Timestamp` was before the Unix epoch.
+    InvalidTimestamp(String),
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::InvalidLength { expected, actual } => write!(
+                f,
+                "invalid length: expected {} bytes, got {}",
+                expected, actual
+            ),
+            FilterError::InvalidHex(s) => write!(f, "invalid hex string: {}", s),
+            FilterError::InvalidChecksum => write!(f, "address failed EIP-55 checksum"),
+            FilterError::InvalidTimestamp(s) => write!(f, "invalid timestamp: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+/// Errors returned while encoding a `Filter`, on top of whatever validation
+/// error was raised while building it.
+#[derive(Debug)]
+pub enum EncodeError {
+    Filter(FilterError),
+    Serde(serde_json::Error),
+    Cbor(serde_cbor::Error),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::Filter(e) => write!(f, "{}", e),
+            EncodeError::Serde(e) => write!(f, "{}", e),
+            EncodeError::Cbor(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EncodeError::Filter(e) => Some(e),
+            EncodeError::Serde(e) => Some(e),
+            EncodeError::Cbor(e) => Some(e),
+        }
+    }
+}
+
+impl From<FilterError> for EncodeError {
+    fn from(e: FilterError) -> Self {
+        EncodeError::Filter(e)
+    }
+}
+
+impl From<serde_json::Error> for EncodeError {
+    fn from(e: serde_json::Error) -> Self {
+        EncodeError::Serde(e)
+    }
+}
+
+impl From<serde_cbor::Error> for EncodeError {
+    fn from(e: serde_cbor::Error) -> Self {
+        EncodeError::Cbor(e)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all(serialize = "PascalCase", deserialize = "PascalCase"))]
 pub struct Filter {
     pub root: Option<NodeRef>,
-    #[serde(skip_serializing)]
+    // Transient builder cursors and the first validation error, if any.
+    // Not part of the wire format; `decode`/`decode_pretty` leave these as
+    // `None` so a re-loaded filter starts with a clean builder state.
+    #[serde(skip)]
     next: Option<NodeRef>,
-    #[serde(skip_serializing)]
+    #[serde(skip)]
     last: Option<NodeRef>,
+    #[serde(skip)]
+    error: Option<FilterError>,
 }
 
-#[derive(Clone, Debug, Serialize)]
-#[serde(rename_all(serialize = "PascalCase"))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all(serialize = "PascalCase", deserialize = "PascalCase"))]
 pub struct Node {
     pub operand: Option<FilterKV>,
     pub operator: Option<Operator>,
@@ -34,8 +121,8 @@ pub struct Node {
 
 type NodeRef = Rc<RefCell<Node>>;
 
-#[derive(Clone, Debug, Serialize)]
-#[serde(rename_all(serialize = "PascalCase"))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all(serialize = "PascalCase", deserialize = "PascalCase"))]
 pub struct FilterKV {
     pub key: String,
 
@@ -43,14 +130,94 @@ pub struct FilterKV {
     pub value: Vec<u8>,
 }
 
-// The API server only accepts base64 encoding for bytes.
+// JSON can't carry raw bytes, so the HTTP API only accepts base64 there.
+// Binary formats like CBOR carry byte strings natively, so we bypass the
+// base64 detour for any non-human-readable serializer.
 mod base64 {
-    use serde::Serialize;
-    use serde::Serializer;
+    use serde::de::{self, Visitor};
+    use serde::{Deserializer, Serialize, Serializer};
+    use std::fmt;
 
     pub fn serialize<S: Serializer>(v: &Vec<u8>, s: S) -> Result<S::Ok, S::Error> {
-        let base64 = base64::encode(v);
-        String::serialize(&base64, s)
+        if s.is_human_readable() {
+            let base64 = base64::encode(v);
+            String::serialize(&base64, s)
+        } else {
+            s.serialize_bytes(v)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        struct BytesOrBase64;
+
+        impl<'de> Visitor<'de> for BytesOrBase64 {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a base64 string or a byte string")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+                base64::decode(v).map_err(E::custom)
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+                Ok(v.to_vec())
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+                Ok(v)
+            }
+        }
+
+        d.deserialize_any(BytesOrBase64)
+    }
+}
+
+/// A typed operand value. Each variant knows how to serialize itself into
+/// the canonical big-endian byte representation the API expects, so new
+/// operand keys (gas, gas_price, nonce, block timestamp, ...) can be added
+/// via `Filter::operand` instead of a bespoke builder method per field.
+#[derive(Clone, Debug)]
+pub enum FilterValue {
+    Bytes(Vec<u8>),
+    Integer(U256),
+    Float(f64),
+    Boolean(bool),
+    /// Unix timestamp in seconds.
+    Timestamp(i64),
+}
+
+impl FilterValue {
+    /// Parses `input` as a timestamp using `format` (a `chrono` strftime
+    /// string), defaulting to `%Y-%m-%dT%H:%M:%S` if omitted. The input is
+    /// read as UTC; the default format accepts no UTC offset or `Z` suffix.
+    pub fn timestamp(input: &str, format: Option<&str>) -> Result<FilterValue, FilterError> {
+        let format = format.unwrap_or("%Y-%m-%dT%H:%M:%S");
+        let parsed = chrono::NaiveDateTime::parse_from_str(input, format)
+            .map_err(|e| FilterError::InvalidTimestamp(e.to_string()))?;
+        Ok(FilterValue::Timestamp(parsed.and_utc().timestamp()))
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>, FilterError> {
+        match self {
+            FilterValue::Bytes(b) => Ok(b.clone()),
+            FilterValue::Integer(u) => Ok(from_u256(*u)),
+            FilterValue::Float(f) => Ok(f.to_bits().to_be_bytes().to_vec()),
+            FilterValue::Boolean(b) => Ok(vec![if *b { 1 } else { 0 }]),
+            // The wire representation is an unsigned big-endian integer, so
+            // a pre-epoch timestamp can't round-trip; reject it rather than
+            // silently wrapping to a huge positive value.
+            FilterValue::Timestamp(secs) => {
+                if *secs < 0 {
+                    return Err(FilterError::InvalidTimestamp(format!(
+                        "timestamp before the Unix epoch: {}",
+                        secs
+                    )));
+                }
+                Ok(from_u256(U256::from(*secs as u64)))
+            }
+        }
     }
 }
 
@@ -60,137 +227,110 @@ impl Filter {
             root: None,
             next: None,
             last: None,
+            error: None,
         }
     }
 
     pub fn to<'a>(&'a mut self, to: &'a str) -> &'a mut Filter {
-        let addr: Address = to.parse().unwrap();
-        let new = Rc::new(RefCell::new(Node {
-            operand: Some(FilterKV {
-                key: String::from("to"),
-                value: addr.as_bytes().to_vec(),
-            }),
-            operator: None,
-            nodes: None,
-        }));
-
-        match &mut self.root {
-            // If there's a root already, append this op to `next`'s children
-            Some(_) => {
-                let mut next = self.next.as_ref().unwrap().borrow_mut();
-                match &mut next.nodes {
-                    Some(children) => {
-                        children.push(new);
-                    }
-                    None => {
-                        let mut v = Vec::new();
-                        v.push(new);
-                        next.nodes = Some(v);
-                    }
-                }
-            }
-            // If no root, create it and point next to it.
-            None => {
-                self.root = Some(new.clone());
-                self.next = Some(new);
-            }
-        };
-        self
+        match validate_address(to) {
+            Ok(addr) => self.push_operand("to", addr.as_bytes().to_vec()),
+            Err(e) => self.fail(e),
+        }
     }
 
     pub fn from<'a>(&'a mut self, from: &'a str) -> &'a mut Filter {
-        let addr: Address = from.parse().unwrap();
-        let new = Rc::new(RefCell::new(Node {
-            operand: Some(FilterKV {
-                key: String::from("from"),
-                value: addr.as_bytes().to_vec(),
-            }),
-            operator: None,
-            nodes: None,
-        }));
-
-        match &mut self.root {
-            // If there's a root already, append this op to `next`'s children
-            Some(_) => {
-                let mut next = self.next.as_ref().unwrap().borrow_mut();
-                match &mut next.nodes {
-                    Some(children) => {
-                        children.push(new);
-                    }
-                    None => {
-                        let mut v = Vec::new();
-                        v.push(new);
-                        next.nodes = Some(v);
-                    }
-                }
-            }
-            // If no root, create it and point next to it.
-            None => {
-                self.root = Some(new.clone());
-                self.next = Some(new);
-            }
-        };
-        self
+        match validate_address(from) {
+            Ok(addr) => self.push_operand("from", addr.as_bytes().to_vec()),
+            Err(e) => self.fail(e),
+        }
     }
 
     pub fn method_id<'a>(&'a mut self, id: &'a str) -> &'a mut Filter {
-        let method_id: Bytes = id.parse().unwrap();
-        let new = Rc::new(RefCell::new(Node {
-            operand: Some(FilterKV {
-                key: String::from("method"),
-                value: method_id.to_vec(),
-            }),
-            operator: None,
-            nodes: None,
-        }));
+        match validate_method_id(id) {
+            Ok(method_id) => self.push_operand("method", method_id.to_vec()),
+            Err(e) => self.fail(e),
+        }
+    }
 
-        match &mut self.root {
-            // If there's a root already, append this op to `next`'s children
-            Some(_) => {
-                let mut next = self.next.as_ref().unwrap().borrow_mut();
-                match &mut next.nodes {
-                    Some(children) => {
-                        children.push(new);
-                    }
-                    None => {
-                        let mut v = Vec::new();
-                        v.push(new);
-                        next.nodes = Some(v);
-                    }
-                }
-            }
-            // If no root, create it and point next to it.
-            None => {
-                self.root = Some(new.clone());
-                self.next = Some(new);
-            }
-        };
+    pub fn value<'a>(&'a mut self, v: U256) -> &'a mut Filter {
+        self.operand("value", FilterValue::Integer(v))
+    }
+
+    /// Adds an operand for an arbitrary key, letting callers filter on
+    /// fields beyond `to`/`from`/`method`/`value` (e.g. `gas`, `nonce`)
+    /// without a dedicated builder method per field.
+    pub fn operand<'a>(&'a mut self, key: &str, value: FilterValue) -> &'a mut Filter {
+        match value.to_bytes() {
+            Ok(bytes) => self.push_operand(key, bytes),
+            Err(e) => self.fail(e),
+        }
+    }
+
+    // Records the first error raised by an operand method. Subsequent
+    // operand calls are no-ops once an error is recorded, and it's
+    // surfaced by `build()`/`encode()`.
+    fn fail(&mut self, e: FilterError) -> &mut Filter {
+        if self.error.is_none() {
+            self.error = Some(e);
+        }
         self
     }
 
-    pub fn value<'a>(&'a mut self, v: U256) -> &'a mut Filter {
-        let bytes = from_u256(v);
+    // The node new operands/operators are appended under: `next` while the
+    // builder chain is live. A filter that was just `decode`d (or otherwise
+    // has no `next` cursor of its own) falls back to `root` — but a bare
+    // leaf root (`operand: Some(..), nodes: None`) can't take children
+    // without becoming a malformed node that's both a leaf and a container,
+    // so it's promoted to an implicit AND wrapping the old leaf first.
+    fn cursor(&mut self) -> Option<NodeRef> {
+        if self.next.is_some() {
+            return self.next.clone();
+        }
+
+        let root = self.root.clone()?;
+        if root.borrow().operand.is_some() {
+            let wrapper = Rc::new(RefCell::new(Node {
+                operand: None,
+                operator: Some(Operator::AND),
+                nodes: Some(vec![root]),
+            }));
+            self.root = Some(wrapper.clone());
+            self.next = Some(wrapper.clone());
+            Some(wrapper)
+        } else {
+            self.next = Some(root.clone());
+            Some(root)
+        }
+    }
+
+    // Appends a validated key/value operand under the cursor, creating the
+    // root if this is the first operand in the filter.
+    fn push_operand(&mut self, key: &str, value: Vec<u8>) -> &mut Filter {
+        if self.error.is_some() {
+            return self;
+        }
+
         let new = Rc::new(RefCell::new(Node {
             operand: Some(FilterKV {
-                key: String::from("value"),
-                value: bytes,
+                key: String::from(key),
+                value,
             }),
             operator: None,
             nodes: None,
         }));
 
-        match &mut self.root {
-            // If there's a root already, append this op to `next`'s children
-            Some(_) => {
-                let mut next = self.next.as_ref().unwrap().borrow_mut();
-                match &mut next.nodes {
+        match self.cursor() {
+            // If there's a cursor already, append this op to its children
+            Some(cursor) => {
+                let mut cursor = cursor.borrow_mut();
+                match &mut cursor.nodes {
                     Some(children) => {
                         children.push(new);
                     }
                     None => {
                         let mut v = Vec::new();
                         v.push(new);
-                        next.nodes = Some(v);
+                        cursor.nodes = Some(v);
                     }
                 }
             }
@@ -207,25 +347,28 @@ impl Filter {
     // as a child of this node. A reference to the last node will be saved in `last`, and you
     // can re-enter that level using `exit()`.
     pub fn and<'a>(&'a mut self) -> &'a mut Filter {
+        if self.error.is_some() {
+            return self;
+        }
+
         let new = Rc::new(RefCell::new(Node {
             operand: None,
             operator: Some(Operator::AND),
             nodes: None,
         }));
 
-        match &mut self.root {
-            Some(_) => {
-                // If there's a root already, append this op to `next`'s children
-                let next = self.next.as_ref().unwrap();
-                let mut next_ptr = next.borrow_mut();
-                match &mut next_ptr.nodes {
+        match self.cursor() {
+            // If there's a cursor already, append this op to its children
+            Some(cursor) => {
+                let mut cursor = cursor.borrow_mut();
+                match &mut cursor.nodes {
                     Some(children) => {
                         children.push(new.clone());
                     }
                     None => {
                         let mut v = Vec::new();
                         v.push(new.clone());
-                        next_ptr.nodes = Some(v);
+                        cursor.nodes = Some(v);
                     }
                 }
             }
@@ -244,25 +387,28 @@ impl Filter {
     // as a child of this node. A reference to the last node will be saved in `last`, and you
     // can re-enter that level using `exit()`.
     pub fn or<'a>(&'a mut self) -> &'a mut Filter {
+        if self.error.is_some() {
+            return self;
+        }
+
         let new = Rc::new(RefCell::new(Node {
             operand: None,
             operator: Some(Operator::OR),
             nodes: None,
         }));
 
-        match &mut self.root {
-            Some(_) => {
-                // If there's a root already, append this op to `next`'s children
-                let next = self.next.as_ref().unwrap();
-                let mut next_ptr = next.borrow_mut();
-                match &mut next_ptr.nodes {
+        match self.cursor() {
+            // If there's a cursor already, append this op to its children
+            Some(cursor) => {
+                let mut cursor = cursor.borrow_mut();
+                match &mut cursor.nodes {
                     Some(children) => {
                         children.push(new.clone());
                     }
                     None => {
                         let mut v = Vec::new();
                         v.push(new.clone());
-                        next_ptr.nodes = Some(v);
+                        cursor.nodes = Some(v);
                     }
                 }
             }
@@ -285,16 +431,180 @@ impl Filter {
         self
     }
 
-    pub fn encode(&self) -> Result<Vec<u8>, serde_json::Error> {
-        serde_json::to_vec(self)
+    pub fn encode(&self) -> Result<Vec<u8>, EncodeError> {
+        if let Some(e) = &self.error {
+            return Err(EncodeError::Filter(e.clone()));
+        }
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn encode_pretty(&self) -> Result<String, EncodeError> {
+        if let Some(e) = &self.error {
+            return Err(EncodeError::Filter(e.clone()));
+        }
+        Ok(serde_json::to_string_pretty(self)?)
     }
 
-    pub fn encode_pretty(&self) -> Result<String, serde_json::Error> {
-        serde_json::to_string_pretty(self)
+    /// Reconstructs a `Filter` previously produced by `encode`. The builder
+    /// cursors (`next`/`last`) come back empty, so follow-up calls like
+    /// `.and()`/`.to(...)` append at the root rather than wherever the
+    /// original builder chain had last descended.
+    pub fn decode(bytes: &[u8]) -> Result<Filter, serde_json::Error> {
+        serde_json::from_slice(bytes)
     }
 
-    pub fn build(&self) -> Filter {
-        self.to_owned()
+    pub fn decode_pretty(s: &str) -> Result<Filter, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+
+    /// Encodes to CBOR instead of JSON. Byte operands are written as native
+    /// CBOR byte strings rather than base64 text, which keeps the filter
+    /// considerably smaller on the wire.
+    pub fn encode_cbor(&self) -> Result<Vec<u8>, EncodeError> {
+        if let Some(e) = &self.error {
+            return Err(EncodeError::Filter(e.clone()));
+        }
+        Ok(serde_cbor::to_vec(self)?)
+    }
+
+    pub fn decode_cbor(bytes: &[u8]) -> Result<Filter, serde_cbor::Error> {
+        serde_cbor::from_slice(bytes)
+    }
+
+    /// Evaluates this filter against `tx` without going through the server
+    /// — handy for unit tests, dry-runs, and double-checking streamed
+    /// results. Returns the same validation error `build()`/`encode()` would
+    /// rather than silently matching everything. An operator node with no
+    /// (or no non-empty) children is vacuously true; a leaf whose key `tx`
+    /// doesn't populate is false.
+    pub fn matches<M: Matchable>(&self, tx: &M) -> Result<bool, FilterError> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        Ok(match &self.root {
+            Some(root) => evaluate_node(root, tx),
+            None => true,
+        })
+    }
+
+    pub fn build(&self) -> Result<Filter, FilterError> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        Ok(self.to_owned())
+    }
+}
+
+fn strip_0x(s: &str) -> &str {
+    s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s)
+}
+
+// Validates a hex address string, mirroring the layered checks used for
+// chain addresses elsewhere: length, then hex-ness, then (for mixed-case
+// input) the EIP-55 checksum.
+fn validate_address(s: &str) -> Result<Address, FilterError> {
+    let hex_part = strip_0x(s);
+    if hex_part.len() != 40 {
+        return Err(FilterError::InvalidLength {
+            expected: 20,
+            actual: hex_part.len() / 2,
+        });
+    }
+    if !hex_part.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(FilterError::InvalidHex(s.to_string()));
+    }
+
+    let is_lower = hex_part.chars().all(|c| !c.is_ascii_uppercase());
+    let is_upper = hex_part.chars().all(|c| !c.is_ascii_lowercase());
+    if !is_lower && !is_upper {
+        verify_checksum(hex_part)?;
+    }
+
+    let bytes = hex::decode(hex_part.to_lowercase()).map_err(|e| FilterError::InvalidHex(e.to_string()))?;
+    Ok(Address::from_slice(&bytes))
+}
+
+// EIP-55: keccak256 the lowercased address, then require each hex letter to
+// be uppercase iff its corresponding nibble of the hash is >= 8.
+fn verify_checksum(addr_hex: &str) -> Result<(), FilterError> {
+    let hash = keccak256(addr_hex.to_lowercase().as_bytes());
+    for (i, c) in addr_hex.chars().enumerate() {
+        if !c.is_ascii_alphabetic() {
+            continue;
+        }
+        let hash_byte = hash[i / 2];
+        let nibble = if i % 2 == 0 { hash_byte >> 4 } else { hash_byte & 0x0f };
+        let should_be_upper = nibble >= 8;
+        if should_be_upper != c.is_ascii_uppercase() {
+            return Err(FilterError::InvalidChecksum);
+        }
+    }
+    Ok(())
+}
+
+fn validate_method_id(s: &str) -> Result<Bytes, FilterError> {
+    let hex_part = strip_0x(s);
+    if !hex_part.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(FilterError::InvalidHex(s.to_string()));
+    }
+    let bytes = hex::decode(hex_part).map_err(|e| FilterError::InvalidHex(e.to_string()))?;
+    if bytes.len() != 4 {
+        return Err(FilterError::InvalidLength {
+            expected: 4,
+            actual: bytes.len(),
+        });
+    }
+    Ok(Bytes::from(bytes))
+}
+
+/// Lets a caller's own transaction type be evaluated against a `Filter`
+/// without converting it to `TxView` first — implement this directly on an
+/// `ethers` transaction type (or any other) to plug it in.
+pub trait Matchable {
+    /// Returns the raw bytes for the given operand key (`to`, `from`,
+    /// `method`, `value`, ...), or `None` if this transaction doesn't
+    /// populate that field.
+    fn field(&self, key: &str) -> Option<&[u8]>;
+}
+
+/// A minimal `Matchable` transaction view, for callers who'd rather map
+/// their transaction type into this than implement `Matchable` themselves.
+#[derive(Clone, Debug, Default)]
+pub struct TxView {
+    pub to: Option<Vec<u8>>,
+    pub from: Option<Vec<u8>>,
+    pub method: Option<[u8; 4]>,
+    pub value: Option<Vec<u8>>,
+}
+
+impl Matchable for TxView {
+    fn field(&self, key: &str) -> Option<&[u8]> {
+        match key {
+            "to" => self.to.as_deref(),
+            "from" => self.from.as_deref(),
+            "method" => self.method.as_ref().map(|m| &m[..]),
+            "value" => self.value.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+fn evaluate_node<M: Matchable>(node: &NodeRef, tx: &M) -> bool {
+    let node = node.borrow();
+
+    if let Some(kv) = &node.operand {
+        return match tx.field(&kv.key) {
+            Some(actual) => actual == kv.value.as_slice(),
+            None => false,
+        };
+    }
+
+    match &node.nodes {
+        Some(children) if !children.is_empty() => match node.operator {
+            Some(Operator::OR) => children.iter().any(|c| evaluate_node(c, tx)),
+            _ => children.iter().all(|c| evaluate_node(c, tx)),
+        },
+        _ => true,
     }
 }
 
@@ -326,4 +636,158 @@ mod tests {
         // .to("0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D");
         println!("{}", new.encode_pretty().unwrap());
     }
+
+    #[test]
+    fn test_to_rejects_bad_checksum() {
+        let mut f = Filter::new();
+        f.to("0x7a250d5630B4cF539739dF2C5dAcb4c659F24abc");
+        assert_eq!(f.build().unwrap_err(), FilterError::InvalidChecksum);
+    }
+
+    #[test]
+    fn test_operand_boolean() {
+        let mut f = Filter::new();
+        f.operand("is_contract_creation", FilterValue::Boolean(true));
+        let built = f.build().unwrap();
+        let root = built.root.unwrap();
+        let kv = root.borrow().operand.clone().unwrap();
+        assert_eq!(kv.key, "is_contract_creation");
+        assert_eq!(kv.value, vec![1]);
+    }
+
+    #[test]
+    fn test_operand_rejects_pre_epoch_timestamp() {
+        let mut f = Filter::new();
+        f.operand("block_timestamp", FilterValue::Timestamp(-1));
+        assert!(matches!(
+            f.build().unwrap_err(),
+            FilterError::InvalidTimestamp(_)
+        ));
+    }
+
+    #[test]
+    fn test_decode_round_trip() {
+        let mut f = Filter::new();
+        f.operand("is_contract_creation", FilterValue::Boolean(true));
+        let built = f.build().unwrap();
+        let encoded = built.encode().unwrap();
+
+        let decoded = Filter::decode(&encoded).unwrap();
+        let root = decoded.root.unwrap();
+        let kv = root.borrow().operand.clone().unwrap();
+        assert_eq!(kv.key, "is_contract_creation");
+        assert_eq!(kv.value, vec![1]);
+    }
+
+    #[test]
+    fn test_decoded_filter_extension_is_enforced_by_matches() {
+        struct TestTx {
+            is_contract_creation: Option<Vec<u8>>,
+            nonce: Option<Vec<u8>>,
+        }
+
+        impl Matchable for TestTx {
+            fn field(&self, key: &str) -> Option<&[u8]> {
+                match key {
+                    "is_contract_creation" => self.is_contract_creation.as_deref(),
+                    "nonce" => self.nonce.as_deref(),
+                    _ => None,
+                }
+            }
+        }
+
+        let mut f = Filter::new();
+        f.operand("is_contract_creation", FilterValue::Boolean(true));
+        let built = f.build().unwrap();
+        let encoded = built.encode().unwrap();
+
+        // Decode a single-leaf filter and extend it with a second operand,
+        // mirroring "load a stored filter, mutate it, re-encode it".
+        let mut decoded = Filter::decode(&encoded).unwrap();
+        decoded.operand("nonce", FilterValue::Integer(U256::from(1)));
+        let extended = decoded.build().unwrap();
+
+        let matching = TestTx {
+            is_contract_creation: Some(vec![1]),
+            nonce: Some(from_u256(U256::from(1))),
+        };
+        assert!(extended.matches(&matching).unwrap());
+
+        let wrong_nonce = TestTx {
+            is_contract_creation: Some(vec![1]),
+            nonce: Some(from_u256(U256::from(2))),
+        };
+        assert!(!extended.matches(&wrong_nonce).unwrap());
+    }
+
+    #[test]
+    fn test_cbor_round_trip() {
+        let mut f = Filter::new();
+        f.operand("is_contract_creation", FilterValue::Boolean(true));
+        let built = f.build().unwrap();
+        let encoded = built.encode_cbor().unwrap();
+
+        let decoded = Filter::decode_cbor(&encoded).unwrap();
+        let root = decoded.root.unwrap();
+        let kv = root.borrow().operand.clone().unwrap();
+        assert_eq!(kv.key, "is_contract_creation");
+        assert_eq!(kv.value, vec![1]);
+    }
+
+    #[test]
+    fn test_matches_and_or() {
+        const TO: &str = "0x7a250d5630b4cf539739df2c5dacb4c659f24bac";
+        const FROM: &str = "0x7a250d5630b4cf539739df2c5dacb4c659f2488d";
+
+        let mut f = Filter::new();
+        f.and().to(TO).or().from(FROM).exit();
+        let f = f.build().unwrap();
+
+        let to_bytes = validate_address(TO).unwrap().as_bytes().to_vec();
+        let from_bytes = validate_address(FROM).unwrap().as_bytes().to_vec();
+
+        let matching = TxView {
+            to: Some(to_bytes.clone()),
+            from: Some(from_bytes),
+            method: None,
+            value: None,
+        };
+        assert!(f.matches(&matching).unwrap());
+
+        let non_matching = TxView {
+            to: Some(to_bytes),
+            from: None,
+            method: None,
+            value: None,
+        };
+        assert!(!f.matches(&non_matching).unwrap());
+    }
+
+    #[test]
+    fn test_matches_empty_filter_is_vacuous() {
+        let f = Filter::new();
+        let tx = TxView::default();
+        assert!(f.matches(&tx).unwrap());
+    }
+
+    #[test]
+    fn test_matches_surfaces_validation_error() {
+        let mut f = Filter::new();
+        f.to("0x7a250d5630B4cF539739dF2C5dAcb4c659F24abc");
+        let tx = TxView::default();
+        assert_eq!(f.matches(&tx).unwrap_err(), FilterError::InvalidChecksum);
+    }
+
+    #[test]
+    fn test_method_id_rejects_wrong_length() {
+        let mut f = Filter::new();
+        f.method_id("0xaabb");
+        assert_eq!(
+            f.build().unwrap_err(),
+            FilterError::InvalidLength {
+                expected: 4,
+                actual: 2
+            }
+        );
+    }
 }